@@ -0,0 +1,81 @@
+//! Native async support for [`EventFD`], backed by tokio's `AsyncFd`.
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use tokio::io::unix::AsyncFd;
+
+use crate::EventFD;
+
+/// An [`EventFD`] driven by a tokio reactor instead of a dedicated thread.
+///
+/// Created by [`EventFD::into_async`], which sets `O_NONBLOCK` on the fd
+/// via `fcntl(2)` regardless of how the `EventFD` was created, since
+/// `AsyncFd` relies on a non-blocking read to detect spurious wakeups.
+///
+/// Unlike [`EventFD::events`], there's no background thread and no spin
+/// loop: readiness is tracked by the reactor, and waiting simply stops when
+/// the `AsyncEventFD` (or a `Stream` over it) is dropped.
+pub struct AsyncEventFD {
+    inner: AsyncFd<EventFD>,
+}
+
+impl AsyncEventFD {
+    pub(crate) fn new(efd: EventFD) -> io::Result<AsyncEventFD> {
+        let flags = OFlag::from_bits_truncate(
+            fcntl(efd.as_raw_fd(), FcntlArg::F_GETFL).map_err(io::Error::from)?,
+        );
+        if !flags.contains(OFlag::O_NONBLOCK) {
+            fcntl(efd.as_raw_fd(), FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))
+                .map_err(io::Error::from)?;
+        }
+
+        Ok(AsyncEventFD {
+            inner: AsyncFd::new(efd)?,
+        })
+    }
+
+    /// Read the current value of the eventfd, waiting asynchronously until
+    /// it becomes readable.
+    pub async fn read(&self) -> io::Result<u64> {
+        loop {
+            let mut guard = self.inner.readable().await?;
+
+            match guard.get_inner().read() {
+                Ok(val) => return Ok(val),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    guard.clear_ready();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Stream for AsyncEventFD {
+    type Item = io::Result<u64>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut guard = match self.inner.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match guard.get_inner().read() {
+                Ok(val) => return Poll::Ready(Some(Ok(val))),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    guard.clear_ready();
+                    continue;
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! A multiplexed waiter for selecting across many [`EventFD`]s.
+
+use std::io;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd};
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::unistd::read;
+
+use crate::EventFD;
+
+/// Waits on several [`EventFD`]s at once, returning the caller's token and
+/// the decremented value for whichever ones became ready.
+///
+/// Registered eventfds are borrowed from the caller for the `'a` lifetime,
+/// so a registered `EventFD` can't be dropped while it's still in the
+/// `EventSet` -- the borrow checker enforces it rather than a doc comment.
+///
+/// This is the common pattern in VMM code, which signals in and out of KVM
+/// through a number of distinct eventfds and wants to drive them all from a
+/// single thread.
+pub struct EventSet<'a, T> {
+    fds: Vec<BorrowedFd<'a>>,
+    tokens: Vec<T>,
+}
+
+impl<'a, T> EventSet<'a, T> {
+    /// Create an empty `EventSet`.
+    pub fn new() -> EventSet<'a, T> {
+        EventSet {
+            fds: Vec::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Register `efd` under `token`, so it's included in future [`Self::wait`]
+    /// calls.
+    pub fn add(&mut self, efd: &'a EventFD, token: T) {
+        self.fds.push(efd.as_fd());
+        self.tokens.push(token);
+    }
+
+    /// Block until one or more registered `EventFD`s are ready, or `timeout`
+    /// elapses (`None` waits indefinitely). Returns the token and read value
+    /// for each fd that fired.
+    ///
+    /// Requires `T: Clone`, since tokens are stored by value and a matching
+    /// one is handed back for every fd that fires.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<Vec<(T, u64)>>
+    where
+        T: Clone,
+    {
+        let deadline = timeout.and_then(|d| Instant::now().checked_add(d));
+        let mut remaining = timeout;
+
+        loop {
+            let poll_timeout = match remaining {
+                Some(d) => {
+                    let millis = d.as_millis().min(i32::MAX as u128) as i32;
+                    PollTimeout::try_from(millis).expect("millis is always non-negative")
+                }
+                None => PollTimeout::NONE,
+            };
+
+            let mut fds: Vec<PollFd> = self
+                .fds
+                .iter()
+                .map(|&fd| PollFd::new(fd, PollFlags::POLLIN))
+                .collect();
+
+            match poll(&mut fds, poll_timeout) {
+                Ok(_) => {
+                    let mut ready = Vec::new();
+                    for (i, pfd) in fds.iter().enumerate() {
+                        let is_readable = pfd
+                            .revents()
+                            .is_some_and(|r| r.contains(PollFlags::POLLIN));
+                        if is_readable {
+                            let mut buf = [0u8; 8];
+                            read(self.fds[i].as_raw_fd(), &mut buf)?;
+                            ready.push((self.tokens[i].clone(), u64::from_ne_bytes(buf)));
+                        }
+                    }
+                    return Ok(ready);
+                }
+                Err(Errno::EINTR) => {
+                    remaining = match deadline {
+                        Some(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+                        None => remaining,
+                    };
+                    continue;
+                }
+                Err(e) => return Err(io::Error::from(e)),
+            }
+        }
+    }
+}
+
+impl<'a, T> Default for EventSet<'a, T> {
+    fn default() -> EventSet<'a, T> {
+        EventSet::new()
+    }
+}
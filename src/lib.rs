@@ -4,28 +4,48 @@
 //! This crate implements a simple binding for Linux eventfd(). See
 //! eventfd(2) for specific details of behaviour.
 
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+#[allow(deprecated)]
 use nix::sys::eventfd::eventfd;
 pub use nix::sys::eventfd::EfdFlags;
-use nix::unistd::{close, dup, read, write};
+use nix::unistd::{read, write};
 
 use std::io;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::fd::OwnedFd;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+mod async_eventfd;
+#[cfg(feature = "tokio")]
+pub use async_eventfd::AsyncEventFD;
+
+mod event_set;
+pub use event_set::EventSet;
+
+/// The result of a call to [`EventFD::read_timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventReadResult {
+    /// The eventfd became readable and yielded this counter value.
+    Count(u64),
+    /// The timeout elapsed before the eventfd became readable.
+    Timeout,
+}
 
 /// An instance of an eventfd. This is a Linux-specific mechanism for publishing
 /// events from the kernel.
 pub struct EventFD {
-    fd: RawFd,
+    fd: OwnedFd,
 }
 
-unsafe impl Send for EventFD {}
-unsafe impl Sync for EventFD {}
-
 impl EventFD {
     /// Create a new [`EventFD`]. Flags is the bitwise OR of EFD_* constants, or
     /// 0 for no flags. The underlying file descriptor is closed when the
     /// `EventFD` instance's lifetime ends.
+    #[allow(deprecated)]
     pub fn new(initval: u32, flags: EfdFlags) -> io::Result<EventFD> {
         Ok(EventFD {
             fd: eventfd(initval, flags)?,
@@ -38,15 +58,48 @@ impl EventFD {
     /// sets it to zero.
     pub fn read(&self) -> io::Result<u64> {
         let mut buf = [0u8; 8];
-        let _ = read(self.fd, &mut buf)?;
+        let _ = read(self.fd.as_raw_fd(), &mut buf)?;
         let val = u64::from_ne_bytes(buf);
         Ok(val)
     }
 
+    /// Read the current value of the eventfd, waiting no longer than
+    /// `timeout`. Returns [`EventReadResult::Timeout`] if the eventfd doesn't
+    /// become readable in time, or [`EventReadResult::Count`] with the value
+    /// otherwise.
+    ///
+    /// A zero (or otherwise exhausted) `timeout` performs a single
+    /// non-blocking `poll(2)`, so this can also be used as a readiness probe
+    /// without needing to create the `EventFD` with `EFD_NONBLOCK`.
+    pub fn read_timeout(&self, timeout: Duration) -> io::Result<EventReadResult> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut remaining = timeout;
+
+        loop {
+            let millis = remaining.as_millis().min(i32::MAX as u128) as i32;
+            let poll_timeout =
+                PollTimeout::try_from(millis).expect("millis is always non-negative");
+            let mut fds = [PollFd::new(self.fd.as_fd(), PollFlags::POLLIN)];
+
+            match poll(&mut fds, poll_timeout) {
+                Ok(0) => return Ok(EventReadResult::Timeout),
+                Ok(_) => return self.read().map(EventReadResult::Count),
+                Err(Errno::EINTR) => {
+                    remaining = match deadline {
+                        Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                        None => remaining,
+                    };
+                    continue;
+                }
+                Err(e) => return Err(io::Error::from(e)),
+            }
+        }
+    }
+
     /// Add to the current value. Blocks if the value would wrap u64.
     pub fn write(&self, val: u64) -> io::Result<()> {
         let buf: [u8; 8] = val.to_ne_bytes();
-        write(self.fd, &buf)?;
+        write(self.fd.as_fd(), &buf)?;
         Ok(())
     }
 
@@ -56,51 +109,137 @@ impl EventFD {
     /// building up a queue of events; if this task blocks on send, the event
     /// state will still update.
     ///
-    /// The task will exit if the receiver end is shut down.
-    ///
-    /// This will be a CPU-spin loop if the EventFD is created non-blocking.
-    ///
-    /// XXX FIXME This has no way of terminating except if the other end closes
-    /// the connection, and only then if we're not blocked in the read()...
-    pub fn events(&self) -> mpsc::Receiver<u64> {
+    /// The worker thread is polling both this eventfd and an internal
+    /// shutdown eventfd, so dropping the returned [`EventStream`] wakes the
+    /// thread and lets it exit deterministically, even if it's currently
+    /// blocked waiting for an event. A `read` error is forwarded over the
+    /// channel rather than panicking the worker thread.
+    pub fn events(&self) -> io::Result<EventStream> {
         let (tx, rx) = mpsc::sync_channel(1);
         let c = self.clone();
+        let shutdown = EventFD::new(0, EfdFlags::empty())?;
+        let worker_shutdown = shutdown.clone();
 
         thread::spawn(move || loop {
+            let mut fds = [
+                PollFd::new(c.fd.as_fd(), PollFlags::POLLIN),
+                PollFd::new(worker_shutdown.fd.as_fd(), PollFlags::POLLIN),
+            ];
+
+            match poll(&mut fds, PollTimeout::NONE) {
+                Ok(_) => (),
+                Err(Errno::EINTR) => continue,
+                Err(_) => break,
+            }
+
+            let shutdown_ready = fds[1]
+                .revents()
+                .is_some_and(|r| r.contains(PollFlags::POLLIN));
+            if shutdown_ready {
+                break;
+            }
+
+            let event_ready = fds[0]
+                .revents()
+                .is_some_and(|r| r.contains(PollFlags::POLLIN));
+            if !event_ready {
+                continue;
+            }
+
             match c.read() {
-                Ok(v) => match tx.send(v) {
-                    Ok(_) => (),
-                    Err(_) => break,
-                },
-                Err(e) => panic!("read failed: {}", e),
+                Ok(v) => {
+                    if tx.send(Ok(v)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    break;
+                }
             }
         });
 
-        rx
+        Ok(EventStream { rx, shutdown })
+    }
+
+    /// Convert into an [`AsyncEventFD`], driven by a tokio reactor instead of
+    /// a dedicated thread.
+    ///
+    /// Requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub fn into_async(self) -> io::Result<AsyncEventFD> {
+        AsyncEventFD::new(self)
+    }
+}
+
+/// A handle to the event stream returned by [`EventFD::events`].
+///
+/// Dropping an `EventStream` signals the worker thread's shutdown eventfd so
+/// it exits promptly, whether or not it's currently blocked waiting for an
+/// event.
+pub struct EventStream {
+    rx: mpsc::Receiver<io::Result<u64>>,
+    shutdown: EventFD,
+}
+
+impl EventStream {
+    /// Block until the next value (or read error) is available.
+    pub fn recv(&self) -> Result<io::Result<u64>, mpsc::RecvError> {
+        self.rx.recv()
+    }
+
+    /// Return an iterator over the stream's values.
+    pub fn iter(&self) -> mpsc::Iter<'_, io::Result<u64>> {
+        self.rx.iter()
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        let _ = self.shutdown.write(1);
     }
 }
 
 impl AsRawFd for EventFD {
     fn as_raw_fd(&self) -> RawFd {
-        self.fd
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for EventFD {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
     }
 }
 
 impl IntoRawFd for EventFD {
     fn into_raw_fd(self) -> RawFd {
-        self.fd
+        self.fd.into_raw_fd()
     }
 }
 
 impl FromRawFd for EventFD {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd: OwnedFd::from_raw_fd(fd),
+        }
     }
 }
 
-impl Drop for EventFD {
-    fn drop(&mut self) {
-        let _ = close(self.fd);
+impl From<OwnedFd> for EventFD {
+    fn from(fd: OwnedFd) -> EventFD {
+        EventFD { fd }
+    }
+}
+
+impl EventFD {
+    /// Construct a linked clone of an existing EventFD, returning an error
+    /// instead of panicking if the underlying `dup(2)` fails (for example,
+    /// if the process has run out of file descriptors).
+    pub fn try_clone(&self) -> io::Result<EventFD> {
+        Ok(EventFD {
+            fd: self.fd.try_clone()?,
+        })
     }
 }
 
@@ -108,18 +247,22 @@ impl Clone for EventFD {
     /// Construct a linked clone of an existing EventFD. Once created, the new
     /// instance interacts with the original in a way that's indistinguishable from
     /// the original.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dup(2)` fails. Use [`EventFD::try_clone`] to handle that
+    /// case instead.
     fn clone(&self) -> EventFD {
-        EventFD {
-            fd: dup(self.fd).unwrap(),
-        }
+        self.try_clone().unwrap()
     }
 }
 
 #[cfg(test)]
 mod test {
     extern crate std;
-    use super::{EfdFlags, EventFD};
+    use super::{EfdFlags, EventFD, EventReadResult, EventSet};
     use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn test_basic() {
@@ -144,6 +287,19 @@ mod test {
         assert_eq!(efd.read().unwrap(), 3);
     }
 
+    #[test]
+    fn test_try_clone() {
+        let efd = match EventFD::new(3, EfdFlags::empty()) {
+            Err(e) => panic!("new failed {}", e),
+            Ok(fd) => fd,
+        };
+        let cefd = efd.try_clone().unwrap();
+
+        assert_eq!(efd.read().unwrap(), 3);
+        assert_eq!(cefd.write(4).unwrap(), ());
+        assert_eq!(efd.read().unwrap(), 4);
+    }
+
     #[test]
     fn test_sema() {
         let efd = match EventFD::new(0, EfdFlags::EFD_SEMAPHORE | EfdFlags::EFD_NONBLOCK) {
@@ -179,8 +335,11 @@ mod test {
         };
         let mut count = 0;
 
+        let stream = efd.events().unwrap();
+
         // only take 10 of 11 so the stream task doesn't block in read and hang the test
-        for v in efd.events().iter().take(10) {
+        for v in stream.iter().take(10) {
+            let v = v.unwrap();
             assert_eq!(v, 1);
             count += v;
         }
@@ -188,6 +347,56 @@ mod test {
         assert_eq!(count, 10)
     }
 
+    #[test]
+    fn test_stream_shutdown() {
+        let efd = match EventFD::new(0, EfdFlags::empty()) {
+            Err(e) => panic!("new failed {}", e),
+            Ok(fd) => fd,
+        };
+
+        let stream = efd.events().unwrap();
+        // Dropping the stream should wake the worker thread even though it's
+        // blocked waiting for an event, rather than leaking it.
+        drop(stream);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let efd = match EventFD::new(0, EfdFlags::empty()) {
+            Err(e) => panic!("new failed {}", e),
+            Ok(fd) => fd,
+        };
+
+        assert_eq!(
+            efd.read_timeout(Duration::from_millis(10)).unwrap(),
+            EventReadResult::Timeout
+        );
+
+        assert_eq!(efd.write(42).unwrap(), ());
+        assert_eq!(
+            efd.read_timeout(Duration::from_secs(1)).unwrap(),
+            EventReadResult::Count(42)
+        );
+    }
+
+    #[test]
+    fn test_event_set() {
+        let a = EventFD::new(0, EfdFlags::empty()).unwrap();
+        let b = EventFD::new(0, EfdFlags::empty()).unwrap();
+
+        let mut set = EventSet::new();
+        set.add(&a, "a");
+        set.add(&b, "b");
+
+        assert_eq!(set.wait(Some(Duration::from_millis(10))).unwrap(), vec![]);
+
+        b.write(5).unwrap();
+        assert_eq!(
+            set.wait(Some(Duration::from_secs(1))).unwrap(),
+            vec![("b", 5)]
+        );
+    }
+
     #[test]
     fn test_chan() {
         let (tx, rx) = std::sync::mpsc::channel();